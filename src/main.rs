@@ -31,6 +31,15 @@ impl Display for Side {
     }
 }
 
+// How the order should be handled once it hits the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderType {
+    Limit,
+    Market,
+    ImmediateOrCancel,
+    FillOrKill,
+}
+
 // Single Order placing
 #[derive(Debug, Clone)]
 pub struct Order {
@@ -40,6 +49,40 @@ pub struct Order {
     pub price: u64,
     pub quantity: u64,
     pub timestamp: u64,
+    pub order_type: OrderType,
+    pub peg: Option<PegSpec>,
+    pub account_id: u64,
+    pub stp_mode: StpMode,
+}
+
+// How to handle a fill that would cross two orders from the same account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StpMode {
+    CancelResting,
+    CancelIncoming,
+    CancelBoth,
+}
+
+// What a pegged order's resting price is computed relative to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PegRef {
+    BestBid,
+    BestAsk,
+    Mid,
+    OraclePrice,
+}
+
+// A resting price that floats with `reference + offset`. The order first
+// rests at its submitted `price`; the peg is re-evaluated, for every
+// reference kind, each time `MatchingEngine::set_oracle_price` is called
+// for its symbol, and not on other book changes.
+#[derive(Debug, Clone, Copy)]
+pub struct PegSpec {
+    pub reference: PegRef,
+    pub offset: i64,
+    // The peg price may never cross this bound: clamped downward for Buy
+    // orders, upward for Sell orders.
+    pub cap: Option<u64>,
 }
 
 impl Display for Order {
@@ -58,13 +101,57 @@ impl Display for Order {
     
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Trade {
     pub buy_order_id: u64,
     pub sell_order_id: u64,
     pub symbol: String,
     pub price: u64,
     pub quantity: u64,
+    pub timestamp: u64,
+}
+
+// Outcome of submitting an order to `SymbolBook::process_order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Filled,
+    Resting,
+    Canceled,
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderOutcome {
+    pub trades: Vec<Trade>,
+    pub status: OrderStatus,
+    // Resting orders canceled by self-trade prevention during this match,
+    // so the caller can notify the affected participant.
+    pub stp_canceled_order_ids: Vec<u64>,
+}
+
+// Reasons an order submission or an amendment of a resting order is
+// rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderError {
+    InvalidTickSize,
+    InvalidLotSize,
+    BelowMinimumSize,
+    UnknownMarket,
+    UnknownOrder,
+}
+
+// Per-symbol trading rules, checked at the start of `process_order` (before
+// any matching) and by `amend_order`.
+#[derive(Debug, Clone, Copy)]
+pub struct MarketConfig {
+    pub tick_size: u64,
+    pub lot_size: u64,
+    pub min_size: u64,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        MarketConfig { tick_size: 1, lot_size: 1, min_size: 1 }
+    }
 }
 
 impl Display for Trade {
@@ -79,24 +166,73 @@ impl Display for Trade {
     }
 }
 
-// Order Book Data Struct 
+// Order Book Data Struct
+
+// Aggregated (price, total quantity) pairs for one side of the book.
+type PriceLevels = Vec<(u64, u64)>;
 
 #[derive(Debug)]
 pub struct SymbolBook {
     bids: BTreeMap<u64, VecDeque<Order>>,
     asks: BTreeMap<u64, VecDeque<Order>>,
     order_lookup: HashMap<u64, (Side, u64)>,
+    config: MarketConfig,
+    // Bumped on every call that mutates the book, so consumers snapshotting
+    // best_bid/best_ask/depth can detect a missed update.
+    seq: u64,
 }
 
 impl SymbolBook {
-    pub fn new() -> Self {
-        SymbolBook { 
+    pub fn new(config: MarketConfig) -> Self {
+        SymbolBook {
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
-            order_lookup: HashMap::new() 
+            order_lookup: HashMap::new(),
+            config,
+            seq: 0,
         }
     }
 
+    pub fn sequence(&self) -> u64 {
+        self.seq
+    }
+
+    // Best resting bid and its total quantity across all orders at that price.
+    pub fn best_bid(&self) -> Option<(u64, u64)> {
+        let (&price, level) = self.bids.iter().next_back()?;
+        Some((price, level.iter().map(|o| o.quantity).sum()))
+    }
+
+    // Best resting ask and its total quantity across all orders at that price.
+    pub fn best_ask(&self) -> Option<(u64, u64)> {
+        let (&price, level) = self.asks.iter().next()?;
+        Some((price, level.iter().map(|o| o.quantity).sum()))
+    }
+
+    pub fn spread(&self) -> Option<u64> {
+        let (bid_price, _) = self.best_bid()?;
+        let (ask_price, _) = self.best_ask()?;
+        Some(ask_price.saturating_sub(bid_price))
+    }
+
+    // Top `levels` aggregated price levels on each side, best price first.
+    pub fn depth(&self, levels: usize) -> (PriceLevels, PriceLevels) {
+        let bids = self
+            .bids
+            .iter()
+            .rev()
+            .take(levels)
+            .map(|(&price, level)| (price, level.iter().map(|o| o.quantity).sum()))
+            .collect();
+        let asks = self
+            .asks
+            .iter()
+            .take(levels)
+            .map(|(&price, level)| (price, level.iter().map(|o| o.quantity).sum()))
+            .collect();
+        (bids, asks)
+    }
+
     fn add_resting_order(&mut self, order: Order) {
         let price = order.price;
         let side = order.side.clone(); // Clone side to avoid partial move
@@ -108,9 +244,10 @@ impl SymbolBook {
         };
 
 
-        let entry = book.entry(price).or_insert_with(VecDeque::new);
+        let entry = book.entry(price).or_default();
         entry.push_back(order);
         self.order_lookup.insert(order_id, (side, price));
+        self.seq += 1;
     }
 
     pub fn cancel_order(&mut self, order_id: u64) -> bool {
@@ -129,14 +266,354 @@ impl SymbolBook {
                     book.remove(&price);
                 }
 
+                if removed {
+                    self.seq += 1;
+                }
                 return removed;
             }
         }
         false
     }
 
-    pub fn process_order(&mut self, mut incoming_order: Order) -> Vec<Trade> {
+    // Cancels every resting order on `side` (or both sides if `None`),
+    // returning how many were removed.
+    pub fn cancel_all(&mut self, side: Option<Side>) -> usize {
+        let mut canceled = 0;
+
+        if side.is_none() || side == Some(Side::Buy) {
+            let ids: Vec<u64> = self.bids.values().flat_map(|level| level.iter().map(|o| o.order_id)).collect();
+            canceled += ids.len();
+            for order_id in ids {
+                self.order_lookup.remove(&order_id);
+            }
+            self.bids.clear();
+        }
+
+        if side.is_none() || side == Some(Side::Sell) {
+            let ids: Vec<u64> = self.asks.values().flat_map(|level| level.iter().map(|o| o.order_id)).collect();
+            canceled += ids.len();
+            for order_id in ids {
+                self.order_lookup.remove(&order_id);
+            }
+            self.asks.clear();
+        }
+
+        if canceled > 0 {
+            self.seq += 1;
+        }
+        canceled
+    }
+
+    // A price change or a quantity increase loses time priority (the order
+    // is removed and re-added at the back of its new level); a pure
+    // quantity decrease keeps it at its current position in its level.
+    pub fn amend_order(&mut self, order_id: u64, new_price: u64, new_quantity: u64) -> Result<Order, OrderError> {
+        if !new_price.is_multiple_of(self.config.tick_size) {
+            return Err(OrderError::InvalidTickSize);
+        }
+        if !new_quantity.is_multiple_of(self.config.lot_size) {
+            return Err(OrderError::InvalidLotSize);
+        }
+        if new_quantity == 0 || new_quantity < self.config.min_size {
+            return Err(OrderError::BelowMinimumSize);
+        }
+
+        let (side, old_price) = match self.order_lookup.get(&order_id) {
+            Some(entry) => entry.clone(),
+            None => return Err(OrderError::UnknownOrder),
+        };
+
+        let book = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+
+        let level = match book.get_mut(&old_price) {
+            Some(level) => level,
+            None => return Err(OrderError::UnknownOrder),
+        };
+
+        let idx = match level.iter().position(|o| o.order_id == order_id) {
+            Some(idx) => idx,
+            None => return Err(OrderError::UnknownOrder),
+        };
+
+        if new_price == old_price && new_quantity <= level[idx].quantity {
+            level[idx].quantity = new_quantity;
+            self.seq += 1;
+            return Ok(level[idx].clone());
+        }
+
+        let mut order = level.remove(idx).unwrap();
+        if level.is_empty() {
+            book.remove(&old_price);
+        }
+
+        order.price = new_price;
+        order.quantity = new_quantity;
+
+        let book = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        book.entry(new_price).or_default().push_back(order.clone());
+        self.order_lookup.insert(order_id, (side, new_price));
+        self.seq += 1;
+
+        Ok(order)
+    }
+
+    fn best_bid_price(&self) -> Option<u64> {
+        self.bids.keys().next_back().copied()
+    }
+
+    fn best_ask_price(&self) -> Option<u64> {
+        self.asks.keys().next().copied()
+    }
+
+    // Resolves a peg to an absolute price: looks up the reference, applies
+    // the offset, rounds down to the nearest tick, then applies the cap.
+    // Returns `None` if the reference isn't available yet (e.g. `BestBid`
+    // with an empty bid side).
+    fn pegged_price(&self, side: &Side, spec: &PegSpec, oracle_price: u64) -> Option<u64> {
+        let reference = match spec.reference {
+            PegRef::BestBid => self.best_bid_price()?,
+            PegRef::BestAsk => self.best_ask_price()?,
+            PegRef::Mid => {
+                let bid = self.best_bid_price()?;
+                let ask = self.best_ask_price()?;
+                (bid + ask) / 2
+            }
+            PegRef::OraclePrice => oracle_price,
+        };
+
+        let raw_price = (reference as i64 + spec.offset).max(0) as u64;
+        let ticked_price = (raw_price / self.config.tick_size) * self.config.tick_size;
+
+        Some(match (spec.cap, side) {
+            (Some(cap), Side::Buy) => ticked_price.min(cap),
+            (Some(cap), Side::Sell) => ticked_price.max(cap),
+            (None, _) => ticked_price,
+        })
+    }
+
+    // Removes a resting order from `old_price` and re-inserts it at the
+    // back of `new_price`, losing time priority like any other re-quote.
+    fn move_resting_order(&mut self, order_id: u64, side: &Side, old_price: u64, new_price: u64) {
+        let book = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+
+        let mut order = match book.get_mut(&old_price) {
+            Some(level) => match level.iter().position(|o| o.order_id == order_id) {
+                Some(idx) => level.remove(idx).unwrap(),
+                None => return,
+            },
+            None => return,
+        };
+
+        if book.get(&old_price).is_some_and(|level| level.is_empty()) {
+            book.remove(&old_price);
+        }
+
+        order.price = new_price;
+        book.entry(new_price).or_default().push_back(order);
+        self.order_lookup.insert(order_id, (side.clone(), new_price));
+        self.seq += 1;
+    }
+
+    // Sweeps the top of book for any bid/ask pair that now crosses (e.g.
+    // after pegged orders were repriced) and executes them at the resting
+    // ask's price, just like a regular limit match. A crossing pair from
+    // the same account is self-trade prevention's concern too, but
+    // neither side is "incoming" the way `process_order` has an
+    // aggressor, so there's no single order's `stp_mode` to defer to:
+    // both resting orders are pulled from the book instead of matched,
+    // mirroring `CancelBoth`.
+    fn match_sweep(&mut self) -> (Vec<Trade>, Vec<u64>) {
+        let mut trades = Vec::new();
+        let mut stp_canceled_order_ids = Vec::new();
+
+        loop {
+            let (bid_price, ask_price) = match (self.best_bid_price(), self.best_ask_price()) {
+                (Some(b), Some(a)) if b >= a => (b, a),
+                _ => break,
+            };
+
+            let mut bid_order = self.bids.get_mut(&bid_price).unwrap().pop_front().unwrap();
+            let mut ask_order = self.asks.get_mut(&ask_price).unwrap().pop_front().unwrap();
+
+            if bid_order.account_id == ask_order.account_id {
+                self.order_lookup.remove(&bid_order.order_id);
+                self.order_lookup.remove(&ask_order.order_id);
+                stp_canceled_order_ids.push(bid_order.order_id);
+                stp_canceled_order_ids.push(ask_order.order_id);
+
+                if self.bids.get(&bid_price).is_some_and(|level| level.is_empty()) {
+                    self.bids.remove(&bid_price);
+                }
+                if self.asks.get(&ask_price).is_some_and(|level| level.is_empty()) {
+                    self.asks.remove(&ask_price);
+                }
+
+                self.seq += 1;
+                continue;
+            }
+
+            let fill_quantity = bid_order.quantity.min(ask_order.quantity);
+
+            trades.push(Trade {
+                buy_order_id: bid_order.order_id,
+                sell_order_id: ask_order.order_id,
+                symbol: bid_order.symbol.clone(),
+                price: ask_price,
+                quantity: fill_quantity,
+                timestamp: bid_order.timestamp.max(ask_order.timestamp),
+            });
+
+            bid_order.quantity -= fill_quantity;
+            ask_order.quantity -= fill_quantity;
+
+            if bid_order.quantity > 0 {
+                self.bids.get_mut(&bid_price).unwrap().push_front(bid_order);
+            } else {
+                self.order_lookup.remove(&bid_order.order_id);
+            }
+
+            if ask_order.quantity > 0 {
+                self.asks.get_mut(&ask_price).unwrap().push_front(ask_order);
+            } else {
+                self.order_lookup.remove(&ask_order.order_id);
+            }
+
+            if self.bids.get(&bid_price).is_some_and(|level| level.is_empty()) {
+                self.bids.remove(&bid_price);
+            }
+            if self.asks.get(&ask_price).is_some_and(|level| level.is_empty()) {
+                self.asks.remove(&ask_price);
+            }
+
+            self.seq += 1;
+        }
+
+        (trades, stp_canceled_order_ids)
+    }
+
+    // Re-evaluates every pegged resting order against the latest oracle
+    // price (and current book), moves any that drifted to a new price
+    // level, then sweeps for newly-crossing orders. Returns the trades
+    // executed and the ids of any resting orders self-trade prevention
+    // pulled from the book during the sweep.
+    pub fn reprice_pegged_orders(&mut self, oracle_price: u64) -> (Vec<Trade>, Vec<u64>) {
+        let mut pegged: Vec<(u64, Side, u64, PegSpec)> = Vec::new();
+
+        for (&price, level) in self.bids.iter() {
+            for order in level.iter() {
+                if let Some(spec) = order.peg {
+                    pegged.push((order.order_id, Side::Buy, price, spec));
+                }
+            }
+        }
+        for (&price, level) in self.asks.iter() {
+            for order in level.iter() {
+                if let Some(spec) = order.peg {
+                    pegged.push((order.order_id, Side::Sell, price, spec));
+                }
+            }
+        }
+
+        for (order_id, side, old_price, spec) in pegged {
+            let new_price = match self.pegged_price(&side, &spec, oracle_price) {
+                Some(p) => p,
+                None => continue,
+            };
+            if new_price != old_price {
+                self.move_resting_order(order_id, &side, old_price, new_price);
+            }
+        }
+
+        self.match_sweep()
+    }
+
+    // Read-only walk of the opposite side's book, accumulating quantity
+    // while the price still crosses `limit_price`, the same way
+    // `process_order` would match against it. Resting quantity that self-
+    // trade prevention would keep from filling against `account_id` is
+    // excluded (skipped for `CancelResting`, since that order is canceled
+    // rather than matched, and matching continues past it; stops
+    // accumulating entirely for `CancelIncoming`/`CancelBoth`, since both
+    // abort the whole order on the first self-trade). Used by FillOrKill
+    // to confirm the whole order is fillable before anything is committed.
+    pub fn fillable_quantity(&self, side: &Side, limit_price: u64, account_id: u64, stp_mode: StpMode) -> u64 {
+        match side {
+            Side::Buy => Self::fillable_from_levels(
+                self.asks.iter().take_while(|&(&price, _)| price <= limit_price).map(|(_, level)| level),
+                account_id,
+                stp_mode,
+            ),
+            Side::Sell => Self::fillable_from_levels(
+                self.bids.iter().rev().take_while(|&(&price, _)| price >= limit_price).map(|(_, level)| level),
+                account_id,
+                stp_mode,
+            ),
+        }
+    }
+
+    fn fillable_from_levels<'a>(
+        levels: impl Iterator<Item = &'a VecDeque<Order>>,
+        account_id: u64,
+        stp_mode: StpMode,
+    ) -> u64 {
+        let mut total = 0;
+        for level in levels {
+            for order in level {
+                if order.account_id == account_id {
+                    match stp_mode {
+                        StpMode::CancelResting => continue,
+                        StpMode::CancelIncoming | StpMode::CancelBoth => return total,
+                    }
+                }
+                total += order.quantity;
+            }
+        }
+        total
+    }
+
+    pub fn process_order(&mut self, mut incoming_order: Order) -> Result<OrderOutcome, OrderError> {
+        // Market orders cross at whatever price is resting, so their own
+        // `price` field is an unused sentinel rather than a tick-aligned
+        // limit; only limit-bearing order types are tick-checked.
+        if incoming_order.order_type != OrderType::Market && !incoming_order.price.is_multiple_of(self.config.tick_size) {
+            return Err(OrderError::InvalidTickSize);
+        }
+        if !incoming_order.quantity.is_multiple_of(self.config.lot_size) {
+            return Err(OrderError::InvalidLotSize);
+        }
+        if incoming_order.quantity < self.config.min_size {
+            return Err(OrderError::BelowMinimumSize);
+        }
+
+        let order_type = incoming_order.order_type;
+
+        if order_type == OrderType::FillOrKill
+            && self.fillable_quantity(
+                &incoming_order.side,
+                incoming_order.price,
+                incoming_order.account_id,
+                incoming_order.stp_mode,
+            ) < incoming_order.quantity
+        {
+            return Ok(OrderOutcome {
+                trades: Vec::new(),
+                status: OrderStatus::Canceled,
+                stp_canceled_order_ids: Vec::new(),
+            });
+        }
+
         let mut trades: Vec<Trade> = Vec::new();
+        let mut stp_canceled_order_ids: Vec<u64> = Vec::new();
+        let mut self_trade_aborted = false;
 
         // Determine which side of the book to match against
         let (incoming_side, target_book) = match incoming_order.side {
@@ -147,7 +624,7 @@ impl SymbolBook {
         while incoming_order.quantity > 0 {
             let best_price_entry = match incoming_side {
                 Side::Buy => target_book.keys().next().cloned(),
-                Side::Sell => target_book.keys().rev().next().cloned(),
+                Side::Sell => target_book.keys().next_back().cloned(),
             };
 
             // If no orders on the target side, break the loop
@@ -156,10 +633,14 @@ impl SymbolBook {
                 None => break,
             };
 
-            // Check for match condition (Price Crossover)
-            let match_found = match incoming_side {
-                Side::Buy => incoming_order.price >= best_price,
-                Side::Sell => incoming_order.price <= best_price,
+            // Check for match condition (Price Crossover). Market orders
+            // match the best available level regardless of their own price.
+            let match_found = match order_type {
+                OrderType::Market => true,
+                _ => match incoming_side {
+                    Side::Buy => incoming_order.price >= best_price,
+                    Side::Sell => incoming_order.price <= best_price,
+                },
             };
 
             if !match_found {
@@ -168,6 +649,37 @@ impl SymbolBook {
 
             let target_level = target_book.get_mut(&best_price).unwrap();
             let mut resting_order = target_level.pop_front().unwrap();
+            self.seq += 1;
+
+            // Self-trade prevention: a fill can't cross two orders from the
+            // same account.
+            if resting_order.account_id == incoming_order.account_id {
+                match incoming_order.stp_mode {
+                    StpMode::CancelResting => {
+                        self.order_lookup.remove(&resting_order.order_id);
+                        stp_canceled_order_ids.push(resting_order.order_id);
+                        if target_level.is_empty() {
+                            target_book.remove(&best_price);
+                        }
+                        continue;
+                    }
+                    StpMode::CancelIncoming => {
+                        target_level.push_front(resting_order);
+                        self_trade_aborted = true;
+                        break;
+                    }
+                    StpMode::CancelBoth => {
+                        self.order_lookup.remove(&resting_order.order_id);
+                        stp_canceled_order_ids.push(resting_order.order_id);
+                        if target_level.is_empty() {
+                            target_book.remove(&best_price);
+                        }
+                        self_trade_aborted = true;
+                        break;
+                    }
+                }
+            }
+
             let fill_quantity = incoming_order.quantity.min(resting_order.quantity);
             let execution_price = resting_order.price;
 
@@ -183,6 +695,7 @@ impl SymbolBook {
                 symbol: incoming_order.symbol.clone(),
                 price: execution_price,
                 quantity: fill_quantity,
+                timestamp: incoming_order.timestamp,
             });
 
             // --- Update quantities and book state ---
@@ -208,11 +721,28 @@ impl SymbolBook {
             }
         }
         
-        if incoming_order.quantity > 0 {
-            self.add_resting_order(incoming_order);
-        }
-        
-        trades
+        // A Limit order rests whatever quantity remains. Market and
+        // ImmediateOrCancel orders never rest: any remainder is dropped.
+        // FillOrKill either fills in full above or never reaches here with
+        // quantity left, since the upfront check already guaranteed it.
+        // A self-trade abort also never rests, regardless of order type.
+        let status = if self_trade_aborted {
+            OrderStatus::Canceled
+        } else if incoming_order.quantity == 0 {
+            OrderStatus::Filled
+        } else {
+            match order_type {
+                OrderType::Limit => {
+                    self.add_resting_order(incoming_order);
+                    OrderStatus::Resting
+                }
+                OrderType::Market | OrderType::ImmediateOrCancel | OrderType::FillOrKill => {
+                    OrderStatus::Canceled
+                }
+            }
+        };
+
+        Ok(OrderOutcome { trades, status, stp_canceled_order_ids })
     }
 
     pub fn print_book(&self, symbol: &str) {
@@ -249,43 +779,148 @@ impl SymbolBook {
 
 }
 
+// OHLCV Candle Aggregation
+
+#[derive(Debug, Clone, Copy)]
+pub struct Candle {
+    pub open: u64,
+    pub high: u64,
+    pub low: u64,
+    pub close: u64,
+    pub volume: u64,
+    pub trade_count: u64,
+}
+
+// Buckets the trade stream into fixed-width time windows per symbol.
+pub struct CandleAggregator {
+    interval_ms: u64,
+    books: HashMap<String, BTreeMap<u64, Candle>>,
+}
+
+impl CandleAggregator {
+    pub fn new(interval_ms: u64) -> Self {
+        CandleAggregator {
+            interval_ms,
+            books: HashMap::new(),
+        }
+    }
+
+    pub fn ingest_trade(&mut self, trade: &Trade) {
+        let bucket = (trade.timestamp / self.interval_ms) * self.interval_ms;
+        let buckets = self.books.entry(trade.symbol.clone()).or_default();
+
+        buckets
+            .entry(bucket)
+            .and_modify(|candle| {
+                candle.high = candle.high.max(trade.price);
+                candle.low = candle.low.min(trade.price);
+                candle.close = trade.price;
+                candle.volume += trade.quantity;
+                candle.trade_count += 1;
+            })
+            .or_insert(Candle {
+                open: trade.price,
+                high: trade.price,
+                low: trade.price,
+                close: trade.price,
+                volume: trade.quantity,
+                trade_count: 1,
+            });
+    }
+
+    pub fn ingest_trades(&mut self, trades: &[Trade]) {
+        for trade in trades {
+            self.ingest_trade(trade);
+        }
+    }
+
+    pub fn candles(&self, symbol: &str, from_ts: u64, to_ts: u64) -> Vec<Candle> {
+        match self.books.get(symbol) {
+            Some(buckets) => buckets.range(from_ts..=to_ts).map(|(_, candle)| *candle).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn latest_candle(&self, symbol: &str) -> Option<Candle> {
+        self.books.get(symbol)?.values().next_back().copied()
+    }
+}
+
 // Matching Engine Wrapper
 
+#[derive(Default)]
 pub struct MatchingEngine {
     books: HashMap<String, SymbolBook>,
     trades: Vec<Trade>,
+    oracle_prices: HashMap<String, u64>,
 }
 
 impl MatchingEngine {
     pub fn new() -> Self {
-        MatchingEngine { 
+        MatchingEngine {
             books: HashMap::new(),
             trades: Vec::new(),
+            oracle_prices: HashMap::new(),
         }
     }
 
-    fn get_or_create_book(&mut self, symbol: &str) -> &mut SymbolBook {
-        self.books.entry(symbol.to_string()).or_insert_with(SymbolBook::new)
+    // Registers a symbol for trading with the given rules, or updates the
+    // rules for a symbol that already has a book.
+    pub fn create_market(&mut self, symbol: &str, config: MarketConfig) {
+        self.books
+            .entry(symbol.to_string())
+            .and_modify(|book| book.config = config)
+            .or_insert_with(|| SymbolBook::new(config));
     }
 
-    pub fn add_order(&mut self, order: Order) {
+    // Updates the reference price `OraclePrice`-pegged orders chase, then
+    // re-pegs and sweeps the affected symbol's book.
+    pub fn set_oracle_price(&mut self, symbol: &str, price: u64) {
+        self.oracle_prices.insert(symbol.to_string(), price);
+
+        if let Some(book) = self.books.get_mut(symbol) {
+            let (trades, stp_canceled_order_ids) = book.reprice_pegged_orders(price);
+            if !trades.is_empty() {
+                println!("--- Executed Trades (Oracle Reprice) ---");
+                for trade in &trades {
+                    println!("  {}", trade);
+                }
+                self.trades.extend(trades);
+            }
+            for canceled_id in &stp_canceled_order_ids {
+                println!("Self-trade prevention canceled resting order ID {}.", canceled_id);
+            }
+        }
+    }
+
+    pub fn add_order(&mut self, order: Order) -> Result<OrderOutcome, OrderError> {
         println!("\n--- New Incoming Order ---");
         println!("Processing: {}", order);
 
         let symbol = order.symbol.clone();
-        let book = self.get_or_create_book(&symbol);
-        
-        let new_trades = book.process_order(order);
+        let book = self.books.get_mut(&symbol).ok_or(OrderError::UnknownMarket)?;
 
-        if !new_trades.is_empty() {
+        let outcome = book.process_order(order)?;
+
+        if !outcome.trades.is_empty() {
             println!("--- Executed Trades ---");
-            for trade in &new_trades {
+            for trade in &outcome.trades {
                 println!("  {}", trade);
             }
-            self.trades.extend(new_trades);
-        } else {
-            println!("No immediate match found. Order resting in book.");
+            self.trades.extend(outcome.trades.clone());
         }
+
+        match outcome.status {
+            OrderStatus::Filled => println!("Order fully filled."),
+            OrderStatus::Resting => println!("No immediate match found. Order resting in book."),
+            OrderStatus::Canceled => println!("Order canceled; unfilled remainder was not rested."),
+        }
+
+        for canceled_id in &outcome.stp_canceled_order_ids {
+            println!("Self-trade prevention canceled resting order ID {}.", canceled_id);
+        }
+
+        Ok(outcome)
     }
 
     pub fn cancel_order(&mut self, order_id: u64, symbol: &str) {
@@ -301,6 +936,41 @@ impl MatchingEngine {
         }
     }
 
+    pub fn cancel_all(&mut self, symbol: &str, side: Option<Side>) -> usize {
+        match self.books.get_mut(symbol) {
+            Some(book) => book.cancel_all(side),
+            None => 0,
+        }
+    }
+
+    pub fn amend_order(&mut self, symbol: &str, order_id: u64, new_price: u64, new_quantity: u64) -> Result<Order, OrderError> {
+        let book = self.books.get_mut(symbol).ok_or(OrderError::UnknownMarket)?;
+        book.amend_order(order_id, new_price, new_quantity)
+    }
+
+    pub fn best_bid(&self, symbol: &str) -> Option<(u64, u64)> {
+        self.books.get(symbol)?.best_bid()
+    }
+
+    pub fn best_ask(&self, symbol: &str) -> Option<(u64, u64)> {
+        self.books.get(symbol)?.best_ask()
+    }
+
+    pub fn spread(&self, symbol: &str) -> Option<u64> {
+        self.books.get(symbol)?.spread()
+    }
+
+    pub fn depth(&self, symbol: &str, levels: usize) -> (PriceLevels, PriceLevels) {
+        match self.books.get(symbol) {
+            Some(book) => book.depth(levels),
+            None => (Vec::new(), Vec::new()),
+        }
+    }
+
+    pub fn sequence(&self, symbol: &str) -> Option<u64> {
+        Some(self.books.get(symbol)?.sequence())
+    }
+
      pub fn print_book(&self, symbol: &str) {
         if let Some(book) = self.books.get(symbol) {
             book.print_book(symbol);
@@ -328,6 +998,7 @@ fn main() {
 
     let symbol = "BTC-USD";
     let mut engine = MatchingEngine::new();
+    engine.create_market(symbol, MarketConfig { tick_size: 10, lot_size: 1, min_size: 1 });
 
     println!("1. Establishing Initial BTC-USD Order Book");
 
@@ -338,8 +1009,12 @@ fn main() {
         side: Side::Sell,
         price: 50020, 
         quantity: 10, 
-        timestamp: generate_timestamp() 
-    });
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 1,
+        stp_mode: StpMode::CancelResting
+    }).expect("order should be accepted");
 
     engine.add_order(Order { 
         order_id: generate_order_id(),
@@ -347,8 +1022,12 @@ fn main() {
         side: Side::Sell,
         price: 50050, 
         quantity: 5, 
-        timestamp: generate_timestamp() 
-    });
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 1,
+        stp_mode: StpMode::CancelResting
+    }).expect("order should be accepted");
 
     engine.add_order(Order { 
         order_id: generate_order_id(),
@@ -356,8 +1035,12 @@ fn main() {
         side: Side::Sell,
         price: 50020, 
         quantity: 5, 
-        timestamp: generate_timestamp() 
-    });
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 1,
+        stp_mode: StpMode::CancelResting
+    }).expect("order should be accepted");
 
     // Bids (Buy)
     engine.add_order(Order { 
@@ -366,8 +1049,12 @@ fn main() {
         side: Side::Buy,
         price: 49980, 
         quantity: 20, 
-        timestamp: generate_timestamp() 
-    });
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 1,
+        stp_mode: StpMode::CancelResting
+    }).expect("order should be accepted");
 
     engine.add_order(Order { 
         order_id: generate_order_id(),
@@ -375,8 +1062,12 @@ fn main() {
         side: Side::Buy,
         price: 49950, 
         quantity: 15, 
-        timestamp: generate_timestamp() 
-    });
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 1,
+        stp_mode: StpMode::CancelResting
+    }).expect("order should be accepted");
 
     engine.add_order(Order { 
         order_id: generate_order_id(),
@@ -384,8 +1075,12 @@ fn main() {
         side: Side::Buy,
         price: 49980, 
         quantity: 10, 
-        timestamp: generate_timestamp() 
-    });
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 1,
+        stp_mode: StpMode::CancelResting
+    }).expect("order should be accepted");
 
     engine.print_book(symbol);
 
@@ -400,8 +1095,12 @@ fn main() {
         side: Side::Buy, 
         price: 50020, 
         quantity: 15, 
-        timestamp: generate_timestamp() 
-    });
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 2,
+        stp_mode: StpMode::CancelResting
+    }).expect("order should be accepted");
 
     engine.print_book(symbol);
 
@@ -418,8 +1117,12 @@ fn main() {
         side: Side::Sell, 
         price: 49900, 
         quantity: 35, 
-        timestamp: generate_timestamp() 
-    });
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 2,
+        stp_mode: StpMode::CancelResting
+    }).expect("order should be accepted");
 
     engine.print_book(symbol);
 
@@ -436,13 +1139,110 @@ fn main() {
         side: Side::Sell, 
         price: 50500, 
         quantity: 50, 
-        timestamp: generate_timestamp() 
-    });
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 1,
+        stp_mode: StpMode::CancelResting
+    }).expect("order should be accepted");
 
     engine.print_book(symbol);
 
 
     // 6. Final Trade Summary
     engine.print_all_trades();
-    
+
+    println!("7. Building 1-Minute Candles From the Trade Stream");
+    let mut candles = CandleAggregator::new(60_000);
+    candles.ingest_trades(&engine.trades);
+    if let Some(candle) = candles.latest_candle(symbol) {
+        println!(
+            "  Latest {} candle -> O:{} H:{} L:{} C:{} V:{} ({} trades)",
+            symbol, candle.open, candle.high, candle.low, candle.close, candle.volume, candle.trade_count
+        );
+    }
+
+    println!("8. Test Oracle-Pegged Resting Order");
+    // Bid pegged at OraclePrice - 20, capped so it can never bid above 50010.
+    engine.add_order(Order {
+        order_id: generate_order_id(),
+        symbol: symbol.to_string(),
+        side: Side::Buy,
+        price: 49990,
+        quantity: 5,
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: Some(PegSpec { reference: PegRef::OraclePrice, offset: -20, cap: Some(50010) }),
+        account_id: 3,
+        stp_mode: StpMode::CancelResting,
+    }).expect("order should be accepted");
+
+    engine.print_book(symbol);
+
+    // Oracle moves up to 50050; the pegged bid should re-price to min(50030, cap 50010) = 50010.
+    engine.set_oracle_price(symbol, 50050);
+
+    engine.print_book(symbol);
+
+    println!("9. Test Order Amendment and Bulk Cancellation");
+    // Quantity decrease only: keeps its place at 50500.
+    let amended = engine.amend_order(symbol, 1008, 50500, 30).expect("order 1008 should exist");
+    println!("  Amended: {}", amended);
+
+    // Price change: loses time priority, moves to the back of the 50400 level.
+    let amended = engine.amend_order(symbol, 1008, 50400, 30).expect("order 1008 should exist");
+    println!("  Amended: {}", amended);
+
+    engine.print_book(symbol);
+
+    let canceled = engine.cancel_all(symbol, Some(Side::Sell));
+    println!("  Canceled {} resting Sell order(s).", canceled);
+
+    engine.print_book(symbol);
+
+    println!("10. Test Self-Trade Prevention");
+    // Resting Sell from account 5.
+    engine.add_order(Order {
+        order_id: generate_order_id(),
+        symbol: symbol.to_string(),
+        side: Side::Sell,
+        price: 50700,
+        quantity: 20,
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 5,
+        stp_mode: StpMode::CancelResting,
+    }).expect("order should be accepted");
+
+    // Incoming Buy from the same account: the resting Sell is canceled by
+    // STP instead of trading against itself, and the Buy rests afterward.
+    engine.add_order(Order {
+        order_id: generate_order_id(),
+        symbol: symbol.to_string(),
+        side: Side::Buy,
+        price: 50700,
+        quantity: 10,
+        timestamp: generate_timestamp(),
+        order_type: OrderType::Limit,
+        peg: None,
+        account_id: 5,
+        stp_mode: StpMode::CancelResting,
+    }).expect("order should be accepted");
+
+    engine.print_book(symbol);
+
+    println!("11. Test Top-of-Book and L2 Depth Queries");
+    if let Some((price, qty)) = engine.best_bid(symbol) {
+        println!("  Best Bid: {} @ {}", qty, price);
+    }
+    if let Some((price, qty)) = engine.best_ask(symbol) {
+        println!("  Best Ask: {} @ {}", qty, price);
+    }
+    println!("  Spread: {:?}", engine.spread(symbol));
+
+    let (bid_depth, ask_depth) = engine.depth(symbol, 5);
+    println!("  Bid Depth: {:?}", bid_depth);
+    println!("  Ask Depth: {:?}", ask_depth);
+    println!("  Sequence: {:?}", engine.sequence(symbol));
 }
\ No newline at end of file